@@ -6,19 +6,38 @@
 
 //  #[cfg(all(test, feature = "nightly"))]
 //  extern crate test;
+#[cfg(unix)]
+extern crate libc;
+extern crate log;
 extern crate time;
 extern crate unix_socket;
 
+use std::cell::RefCell;
 use std::convert::AsRef;
+use std::fmt::Write as FmtWrite;
 use std::io::{ self, Write };
-use std::net::{ Ipv4Addr, UdpSocket, SocketAddr, SocketAddrV4 };
-use std::path::Path;
+use std::net::{ Ipv4Addr, TcpStream, UdpSocket, SocketAddr, SocketAddrV4 };
+use std::path::{ Path, PathBuf };
 use std::ops::Deref;
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicBool, Ordering };
 use time::Tm;
 use unix_socket::UnixStream;
 
+#[cfg(unix)]
+use std::ffi::CString;
+#[cfg(unix)]
+use std::mem;
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
 static NIL: &'static str = "-";
 
+thread_local! {
+  // reused across calls to Syslog::log to avoid a fresh allocation per record
+  static LINE_BUF: RefCell<String> = RefCell::new(String::new());
+}
+
 /// Syslog [Facilities](https://tools.ietf.org/html/rfc5424#page-10)
 #[derive(Copy,Clone)]
 pub enum Facility {
@@ -65,6 +84,7 @@ pub enum Facility {
 }
 
 /// Syslog [Severities](https://tools.ietf.org/html/rfc5424#page-11)
+#[derive(Copy,Clone)]
 pub enum Severity {
   /// Emergency Severity
   EMERGENCY,
@@ -87,22 +107,207 @@ pub enum Severity {
 /// Result of log operations
 pub type Result = io::Result<()>;
 
-trait Transport {
-  fn send(&mut self, line: &str) -> Result;
+/// A single SD-ELEMENT of the STRUCTURED-DATA field, as defined by
+/// [rfc5424#section-6.3](https://tools.ietf.org/html/rfc5424#section-6.3)
+#[derive(Clone)]
+pub struct StructuredData {
+  id: String,
+  params: Vec<(String, String)>
+}
+
+impl StructuredData {
+  /// Creates a new SD-ELEMENT identified by the given SD-ID and carrying
+  /// no PARAM-NAME/PARAM-VALUE pairs
+  pub fn new(id: &str) -> StructuredData {
+    StructuredData { id: id.to_owned(), params: Vec::new() }
+  }
+
+  /// Returns a new StructuredData with the given PARAM-NAME/PARAM-VALUE
+  /// pair appended
+  pub fn param(mut self, name: &str, value: &str) -> StructuredData {
+    self.params.push((name.to_owned(), value.to_owned()));
+    self
+  }
+
+  fn render(&self, out: &mut String) {
+    out.push('[');
+    out.push_str(&self.id);
+    for &(ref name, ref value) in &self.params {
+      out.push(' ');
+      out.push_str(name);
+      out.push_str("=\"");
+      escape_sd_param_value(value, out);
+      out.push('"');
+    }
+    out.push(']');
+  }
+}
+
+// escapes the three reserved characters of a PARAM-VALUE as required by
+// rfc5424#section-6.3.3
+fn escape_sd_param_value(value: &str, out: &mut String) {
+  for c in value.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      ']' => out.push_str("\\]"),
+      _ => out.push(c)
+    }
+  }
+}
+
+// Send so a Syslog can be wrapped in a Mutex and shared across threads,
+// as the log::Log facade requires
+trait Transport: Send {
+  // priority is the already-computed PRI value, line is the fully
+  // formatted frame (socket-based transports write this verbatim), msg
+  // is just the message body, and app/pid are the Syslog's current
+  // app-name/pid (used by transports, like the POSIX backend, that let
+  // the platform supply the rest of the header)
+  fn send(&mut self, priority: u8, line: &str, msg: &str, app: Option<&str>, pid: Option<&str>) -> Result;
 }
 
 impl Transport for (UdpSocket, SocketAddr) {
-  fn send(&mut self, line: &str) -> Result {
+  fn send(&mut self, _priority: u8, line: &str, _msg: &str, _app: Option<&str>, _pid: Option<&str>) -> Result {
     self.0.send_to(line.as_bytes(), &self.1).map(|_| ())
   }
 }
 
 impl Transport for UnixStream {
-  fn send(&mut self, line: &str) -> Result {
+  fn send(&mut self, _priority: u8, line: &str, _msg: &str, _app: Option<&str>, _pid: Option<&str>) -> Result {
     self.write_all(line.as_bytes())
   }
 }
 
+/// Framing mode used by the TCP transport, as defined by
+/// [rfc6587](https://tools.ietf.org/html/rfc6587)
+#[derive(Copy,Clone)]
+pub enum TcpFraming {
+  /// Prefixes each frame with its byte length in ASCII followed by a
+  /// space, e.g. `47 <134>1 ...`
+  /// ([rfc6587#section-3.4.1](https://tools.ietf.org/html/rfc6587#section-3.4.1))
+  OctetCounting,
+  /// Appends a trailing `\n` after each frame instead
+  /// ([rfc6587#section-3.4.2](https://tools.ietf.org/html/rfc6587#section-3.4.2))
+  NonTransparent
+}
+
+struct TcpTransport {
+  writer: io::BufWriter<TcpStream>,
+  framing: TcpFraming
+}
+
+impl Transport for TcpTransport {
+  fn send(&mut self, _priority: u8, line: &str, _msg: &str, _app: Option<&str>, _pid: Option<&str>) -> Result {
+    match self.framing {
+      TcpFraming::OctetCounting => write!(self.writer, "{} {}", line.len(), line)?,
+      TcpFraming::NonTransparent => write!(self.writer, "{}\n", line)?
+    };
+    self.writer.flush()
+  }
+}
+
+// routes records through the platform's own openlog(3)/syslog(3)/closelog(3),
+// letting the local syslogd assemble the timestamp/hostname/PRI instead of
+// writing our own RFC5424 frame over a socket
+#[cfg(unix)]
+struct PosixTransport {
+  facility: Facility,
+  opened: bool
+}
+
+#[cfg(unix)]
+impl PosixTransport {
+  fn new(facility: Facility) -> PosixTransport {
+    PosixTransport { facility: facility, opened: false }
+  }
+
+  // deferred until the first send(), so it can pick up whatever
+  // app-name/pid the Syslog builder chain ended up with
+  fn open(&mut self, app: Option<&str>, pid: Option<&str>) {
+    let ident = CString::new(app.unwrap_or("sysly")).unwrap_or_else(|_| CString::new("sysly").unwrap());
+    let mut logopt = libc::LOG_NDELAY;
+    if pid.is_some() {
+      logopt |= libc::LOG_PID;
+    }
+    unsafe {
+      libc::openlog(ident.as_ptr(), logopt, self.facility as libc::c_int);
+    }
+    // openlog(3) keeps the ident pointer rather than copying it, so the
+    // CString must outlive the logging session
+    mem::forget(ident);
+    self.opened = true;
+  }
+}
+
+#[cfg(unix)]
+impl Transport for PosixTransport {
+  fn send(&mut self, priority: u8, _line: &str, msg: &str, app: Option<&str>, pid: Option<&str>) -> Result {
+    if !self.opened {
+      self.open(app, pid);
+    }
+    let cmsg = match CString::new(msg) {
+      Ok(s) => s,
+      Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "syslog message contained a nul byte"))
+    };
+    unsafe {
+      libc::syslog(priority as libc::c_int, b"%s\0".as_ptr() as *const libc::c_char, cmsg.as_ptr());
+    }
+    Ok(())
+  }
+}
+
+#[cfg(unix)]
+impl Drop for PosixTransport {
+  fn drop(&mut self) {
+    if self.opened {
+      unsafe {
+        libc::closelog();
+      }
+    }
+  }
+}
+
+#[cfg(unix)]
+struct UnixDatagramTransport {
+  socket: UnixDatagram,
+  path: PathBuf,
+  // degrade to writing the formatted line to stderr instead of
+  // returning an error when the datagram socket isn't reachable
+  stderr_fallback: bool
+}
+
+#[cfg(unix)]
+impl Transport for UnixDatagramTransport {
+  fn send(&mut self, _priority: u8, line: &str, _msg: &str, _app: Option<&str>, _pid: Option<&str>) -> Result {
+    match self.socket.send_to(line.as_bytes(), &self.path) {
+      Ok(_) => Ok(()),
+      Err(e) => {
+        if self.stderr_fallback {
+          writeln!(io::stderr(), "{}", line)
+        } else {
+          Err(e)
+        }
+      }
+    }
+  }
+}
+
+/// Syslog wire format to emit, selectable via `Syslog::protocol`
+#[derive(Copy,Clone)]
+pub enum Protocol {
+  /// The modern format defined by
+  /// [rfc5424](https://tools.ietf.org/html/rfc5424), the crate's default
+  Rfc5424,
+  /// The legacy BSD format defined by
+  /// [rfc3164](https://tools.ietf.org/html/rfc3164), understood by older
+  /// collectors and appliances
+  Rfc3164
+}
+
+static MONTHS: [&'static str; 12] =
+  ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
 /// A rust interface for Syslog, a standard unix system logging service
 pub struct Syslog {
   /// A Syslog facility to target when logging
@@ -116,9 +321,22 @@ pub struct Syslog {
   /// An optional proc-id appended to Syslog messages as defined by
   /// [rfc5424#section-6.2.6](https://tools.ietf.org/html/rfc5424#section-6.2.6)
   pid: Option<String>,
-  /// An optional msg-id appended to Syslog messages as defined by 
+  /// An optional msg-id appended to Syslog messages as defined by
   /// [rfc5424#section-6.2.7](https://tools.ietf.org/html/rfc5424#section-6.2.7)
   msgid: Option<String>,
+  /// STRUCTURED-DATA elements appended to Syslog messages as defined by
+  /// [rfc5424#section-6.3](https://tools.ietf.org/html/rfc5424#section-6.3)
+  structured_data: Vec<StructuredData>,
+  /// The wire format used to render messages
+  protocol: Protocol,
+  /// An optional cap on the length of a rendered record; longer records
+  /// have their MSG portion truncated on a char boundary
+  max_len: Option<usize>,
+  /// Whether to prefix the MSG field with a UTF-8 BOM, as permitted by
+  /// [rfc5424#section-6.4](https://tools.ietf.org/html/rfc5424#section-6.4)
+  bom: bool,
+  /// Whether the most recently logged record was truncated to fit max_len
+  truncated: bool,
   transport: Box<Transport>
 }
 
@@ -138,6 +356,11 @@ impl Syslog {
         app: None,
         pid: None,
         msgid: None,
+        structured_data: Vec::new(),
+        protocol: Protocol::Rfc5424,
+        max_len: None,
+        bom: false,
+        truncated: false,
         transport: Box::new(tup)
       }
   }
@@ -162,9 +385,112 @@ impl Syslog {
       app: None,
       pid: None,
       msgid: None,
+      structured_data: Vec::new(),
+      protocol: Protocol::Rfc5424,
+      max_len: None,
+      bom: false,
+      truncated: false,
       transport: Box::new(stream)
     }
   }
+
+  /// Factory for a Syslog appender that writes to a remote Syslog
+  /// collector over TCP, framing each record with RFC 6587
+  /// octet-counting
+  pub fn tcp(addr: SocketAddr) -> Syslog {
+    Syslog::tcp_with_framing(addr, TcpFraming::OctetCounting)
+  }
+
+  /// Same as `tcp`, but lets the caller pick the RFC 6587 framing mode,
+  /// e.g. `NonTransparent` for collectors that expect a trailing
+  /// newline instead of octet-counting
+  pub fn tcp_with_framing(addr: SocketAddr, framing: TcpFraming) -> Syslog {
+    let stream =
+      match TcpStream::connect(addr) {
+        Err(e) => panic!("error connecting to {}: {}", addr, e),
+        Ok(s) => s
+      };
+    let transport = TcpTransport { writer: io::BufWriter::new(stream), framing: framing };
+    Syslog {
+      facility: Facility::USER,
+      host: None,
+      app: None,
+      pid: None,
+      msgid: None,
+      structured_data: Vec::new(),
+      protocol: Protocol::Rfc5424,
+      max_len: None,
+      bom: false,
+      truncated: false,
+      transport: Box::new(transport)
+    }
+  }
+
+  /// Factory for a Syslog appender that routes through the platform's
+  /// own `openlog`/`syslog`/`closelog`, avoiding the need to manage a
+  /// socket to the local syslogd ourselves
+  #[cfg(unix)]
+  pub fn posix() -> Syslog {
+    let facility = Facility::USER;
+    let transport = PosixTransport::new(facility);
+    Syslog {
+      facility: facility,
+      host: None,
+      app: None,
+      pid: None,
+      msgid: None,
+      structured_data: Vec::new(),
+      protocol: Protocol::Rfc5424,
+      max_len: None,
+      bom: false,
+      truncated: false,
+      transport: Box::new(transport)
+    }
+  }
+
+  /// Factory for a Syslog appender that writes to a host-local Syslog
+  /// daemon listening on a unix datagram socket hosted at the given
+  /// Path, as most Linux/BSD syslog daemons do at `/dev/log`. A failed
+  /// send returns an error; see `unixgram_with_stderr_fallback` to
+  /// degrade to stderr instead
+  #[cfg(unix)]
+  pub fn unixgram<P: AsRef<Path>>(path: P) -> Syslog {
+    Syslog::unixgram_with_stderr_fallback(path, false)
+  }
+
+  /// Same as `unixgram`, but when `stderr_fallback` is true a failed
+  /// datagram write degrades to writing the formatted line to standard
+  /// error instead of returning an error to the call site
+  #[cfg(unix)]
+  pub fn unixgram_with_stderr_fallback<P: AsRef<Path>>(path: P, stderr_fallback: bool) -> Syslog {
+    let socket =
+      match UnixDatagram::unbound() {
+        Err(e) => panic!("error creating unix datagram socket: {}", e),
+        Ok(s) => s
+      };
+    let transport = UnixDatagramTransport { socket: socket, path: path.as_ref().to_path_buf(), stderr_fallback: stderr_fallback };
+    Syslog {
+      facility: Facility::USER,
+      host: None,
+      app: None,
+      pid: None,
+      msgid: None,
+      structured_data: Vec::new(),
+      protocol: Protocol::Rfc5424,
+      max_len: None,
+      bom: false,
+      truncated: false,
+      transport: Box::new(transport)
+    }
+  }
+
+  /// Same as `unixgram`, connecting to the conventional `/dev/log`
+  /// datagram socket most Linux/BSD syslog daemons listen on
+  #[cfg(unix)]
+  pub fn devlog() -> Syslog {
+    Syslog::unixgram("/dev/log")
+  }
+
   /// Returns a new Syslog appender configured to append with
   /// the provided Facility
   pub fn facility(self, facility: Facility) -> Syslog {
@@ -174,6 +500,11 @@ impl Syslog {
       app: self.app,
       pid: self.pid,
       msgid: self.msgid,
+      structured_data: self.structured_data,
+      protocol: self.protocol,
+      max_len: self.max_len,
+      bom: self.bom,
+      truncated: self.truncated,
       transport: self.transport
     }
   }
@@ -187,6 +518,11 @@ impl Syslog {
       app: self.app,
       pid: self.pid,
       msgid: self.msgid,
+      structured_data: self.structured_data,
+      protocol: self.protocol,
+      max_len: self.max_len,
+      bom: self.bom,
+      truncated: self.truncated,
       transport: self.transport
     }
   }
@@ -200,6 +536,11 @@ impl Syslog {
       app: Some(app.to_owned()),
       pid: self.pid,
       msgid: self.msgid,
+      structured_data: self.structured_data,
+      protocol: self.protocol,
+      max_len: self.max_len,
+      bom: self.bom,
+      truncated: self.truncated,
       transport: self.transport
     }
   }
@@ -213,6 +554,11 @@ impl Syslog {
       app: self.app,
       pid: Some(pid.to_owned()),
       msgid: self.msgid,
+      structured_data: self.structured_data,
+      protocol: self.protocol,
+      max_len: self.max_len,
+      bom: self.bom,
+      truncated: self.truncated,
       transport: self.transport
     }
   }
@@ -226,6 +572,94 @@ impl Syslog {
       app: self.app,
       pid: self.pid,
       msgid: Some(id.to_string()),
+      structured_data: self.structured_data,
+      protocol: self.protocol,
+      max_len: self.max_len,
+      bom: self.bom,
+      truncated: self.truncated,
+      transport: self.transport
+    }
+  }
+
+  /// Returns a new Syslog appender configured to render messages using
+  /// the given Protocol
+  pub fn protocol(self, protocol: Protocol) -> Syslog {
+    Syslog {
+      facility: self.facility,
+      host: self.host,
+      app: self.app,
+      pid: self.pid,
+      msgid: self.msgid,
+      structured_data: self.structured_data,
+      protocol: protocol,
+      max_len: self.max_len,
+      bom: self.bom,
+      truncated: self.truncated,
+      transport: self.transport
+    }
+  }
+
+  /// Returns a new Syslog appender that truncates the MSG portion of any
+  /// record (on a char boundary, never touching the header fields) once
+  /// the rendered record exceeds `max_len` bytes. Check `truncated()`
+  /// after logging to see whether the last record was cut
+  pub fn max_len(self, max_len: usize) -> Syslog {
+    Syslog {
+      facility: self.facility,
+      host: self.host,
+      app: self.app,
+      pid: self.pid,
+      msgid: self.msgid,
+      structured_data: self.structured_data,
+      protocol: self.protocol,
+      max_len: Some(max_len),
+      bom: self.bom,
+      truncated: self.truncated,
+      transport: self.transport
+    }
+  }
+
+  /// Returns a new Syslog appender that prefixes the MSG field with a
+  /// UTF-8 BOM, as permitted by
+  /// [rfc5424#section-6.4](https://tools.ietf.org/html/rfc5424#section-6.4)
+  pub fn bom(self, bom: bool) -> Syslog {
+    Syslog {
+      facility: self.facility,
+      host: self.host,
+      app: self.app,
+      pid: self.pid,
+      msgid: self.msgid,
+      structured_data: self.structured_data,
+      protocol: self.protocol,
+      max_len: self.max_len,
+      bom: bom,
+      truncated: self.truncated,
+      transport: self.transport
+    }
+  }
+
+  /// Returns whether the most recently logged record was truncated to
+  /// fit the configured `max_len`
+  pub fn truncated(&self) -> bool {
+    self.truncated
+  }
+
+  /// Returns a new Syslog appender with the given STRUCTURED-DATA element
+  /// appended to its SD-ELEMENTs
+  pub fn structured_data(self, sd: StructuredData) -> Syslog {
+    let mut structured_data = self.structured_data;
+    structured_data.push(sd);
+    Syslog {
+      facility: self.facility,
+      host: self.host,
+      app: self.app,
+      pid: self.pid,
+      msgid: self.msgid,
+      structured_data: structured_data,
+      protocol: self.protocol,
+      max_len: self.max_len,
+      bom: self.bom,
+      truncated: self.truncated,
       transport: self.transport
     }
   }
@@ -271,21 +705,102 @@ impl Syslog {
   }
 
   fn log(&mut self, severity: Severity,  msg: &str) -> Result {
-    let formatted = Syslog::line(
-        self.facility.clone(), severity, time::now(), self.host.as_ref().map(Deref::deref), self.app.as_ref().map(Deref::deref), self.pid.as_ref().map(Deref::deref), self.msgid.as_ref().map(Deref::deref), msg);
-    self.transport.send(&formatted)
+    let priority = Syslog::priority(self.facility.clone(), severity);
+    LINE_BUF.with(|cell| {
+      let mut buf = cell.borrow_mut();
+      buf.clear();
+      let header_len = match self.protocol {
+        Protocol::Rfc5424 => Syslog::write_line_5424(
+          &mut buf, self.facility.clone(), severity, time::now(), self.host.as_ref().map(Deref::deref), self.app.as_ref().map(Deref::deref), self.pid.as_ref().map(Deref::deref), self.msgid.as_ref().map(Deref::deref), &self.structured_data, msg, self.bom),
+        Protocol::Rfc3164 => Syslog::write_line_3164(
+          &mut buf, self.facility.clone(), severity, time::now(), self.host.as_ref().map(Deref::deref), self.app.as_ref().map(Deref::deref), self.pid.as_ref().map(Deref::deref), msg)
+      };
+      self.truncated = Syslog::truncate_to(&mut buf, header_len, self.max_len);
+      self.transport.send(priority, &buf, msg, self.app.as_ref().map(Deref::deref), self.pid.as_ref().map(Deref::deref))
+    })
+  }
+
+  #[cfg(test)]
+  fn line(facility: Facility, severity: Severity, timestamp: Tm, host: Option<&str>, app: Option<&str>, pid: Option<&str>, msgid: Option<&str>, structured_data: &[StructuredData], msg: &str) -> String {
+    let mut buf = String::new();
+    Syslog::write_line_5424(&mut buf, facility, severity, timestamp, host, app, pid, msgid, structured_data, msg, false);
+    buf
   }
 
-  fn line(facility: Facility, severity: Severity, timestamp: Tm, host: Option<&str>, app: Option<&str>, pid: Option<&str>, msgid: Option<&str>, msg: &str) -> String {
-    format!(
-      "<{:?}>1 {} {} {} {} {} {}",
+  // renders the rfc5424 record into `buf`, returning the length of `buf` after any BOM
+  // and immediately before MSG is appended, so callers can truncate MSG without touching
+  // the header fields or the BOM
+  fn write_line_5424(buf: &mut String, facility: Facility, severity: Severity, timestamp: Tm, host: Option<&str>, app: Option<&str>, pid: Option<&str>, msgid: Option<&str>, structured_data: &[StructuredData], msg: &str, bom: bool) -> usize {
+    let mut sd = String::new();
+    if structured_data.is_empty() {
+      sd.push_str(NIL);
+    } else {
+      for elem in structured_data {
+        elem.render(&mut sd);
+      }
+    }
+    write!(buf,
+      "<{:?}>1 {} {} {} {} {} {} ",
         Syslog::priority(facility, severity),
         timestamp.rfc3339(),
         host.unwrap_or(NIL),
         app.unwrap_or(NIL),
         pid.unwrap_or(NIL),
         msgid.unwrap_or(NIL),
-        msg)
+        sd).unwrap();
+    if bom {
+      buf.push('\u{feff}');
+    }
+    let header_len = buf.len();
+    buf.push_str(msg);
+    header_len
+  }
+
+  // renders the legacy rfc3164 (BSD) format: <PRI>Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG,
+  // with no version, msgid or structured-data since rfc3164 has no fields for them
+  #[cfg(test)]
+  fn line_3164(facility: Facility, severity: Severity, timestamp: Tm, host: Option<&str>, app: Option<&str>, pid: Option<&str>, msg: &str) -> String {
+    let mut buf = String::new();
+    Syslog::write_line_3164(&mut buf, facility, severity, timestamp, host, app, pid, msg);
+    buf
+  }
+
+  fn write_line_3164(buf: &mut String, facility: Facility, severity: Severity, timestamp: Tm, host: Option<&str>, app: Option<&str>, pid: Option<&str>, msg: &str) -> usize {
+    let tag = match pid {
+      Some(pid) => format!("{}[{}]", app.unwrap_or(NIL), pid),
+      None => app.unwrap_or(NIL).to_owned()
+    };
+    write!(buf,
+      "<{:?}>{} {} {}: ",
+        Syslog::priority(facility, severity),
+        Syslog::rfc3164_timestamp(&timestamp),
+        host.unwrap_or(NIL),
+        tag).unwrap();
+    let header_len = buf.len();
+    buf.push_str(msg);
+    header_len
+  }
+
+  // truncates `buf` to `max_len` bytes on a UTF-8 char boundary, never cutting into
+  // the header (the first `header_len` bytes); returns whether truncation happened
+  fn truncate_to(buf: &mut String, header_len: usize, max_len: Option<usize>) -> bool {
+    match max_len {
+      Some(max_len) if buf.len() > max_len => {
+        let mut cut = if max_len < header_len { header_len } else { max_len };
+        while cut > header_len && !buf.is_char_boundary(cut) {
+          cut -= 1;
+        }
+        buf.truncate(cut);
+        true
+      },
+      _ => false
+    }
+  }
+
+  // Mmm dd hh:mm:ss, with the day space-padded and no year, as required by rfc3164#section-4.1.2
+  fn rfc3164_timestamp(timestamp: &Tm) -> String {
+    format!("{} {:2} {:02}:{:02}:{:02}",
+      MONTHS[timestamp.tm_mon as usize], timestamp.tm_mday, timestamp.tm_hour, timestamp.tm_min, timestamp.tm_sec)
   }
 
   // computes the priority of a message based on a facility and severity
@@ -294,18 +809,206 @@ impl Syslog {
   }
 }
 
+// maps a log::Level onto the closest Syslog Severity
+fn severity_from_level(level: log::Level) -> Severity {
+  match level {
+    log::Level::Error => Severity::ERROR,
+    log::Level::Warn  => Severity::WARNING,
+    log::Level::Info  => Severity::INFO,
+    log::Level::Debug => Severity::DEBUG,
+    log::Level::Trace => Severity::DEBUG
+  }
+}
+
+/// Configuration for installing a Syslog appender as the global `log`
+/// backend via `init_with`
+pub struct LogConfig {
+  facility: Option<Facility>,
+  host: Option<String>,
+  app: Option<String>,
+  pid: Option<String>,
+  max_level: log::LevelFilter,
+  stderr: bool
+}
+
+impl LogConfig {
+  /// Creates a default LogConfig: no facility/host/app/pid overrides (the
+  /// Syslog appender's own settings are kept), a max level of Info and no
+  /// stderr mirror
+  pub fn new() -> LogConfig {
+    LogConfig {
+      facility: None,
+      host: None,
+      app: None,
+      pid: None,
+      max_level: log::LevelFilter::Info,
+      stderr: false
+    }
+  }
+
+  /// Returns a new LogConfig configured to log with the provided Facility,
+  /// overriding whatever facility the Syslog appender was built with
+  pub fn facility(self, facility: Facility) -> LogConfig {
+    LogConfig { facility: Some(facility), host: self.host, app: self.app, pid: self.pid, max_level: self.max_level, stderr: self.stderr }
+  }
+
+  /// Returns a new LogConfig configured with the provided host,
+  /// overriding whatever host the Syslog appender was built with
+  pub fn host(self, host: &str) -> LogConfig {
+    LogConfig { facility: self.facility, host: Some(host.to_owned()), app: self.app, pid: self.pid, max_level: self.max_level, stderr: self.stderr }
+  }
+
+  /// Returns a new LogConfig configured with the provided app-name,
+  /// overriding the record's target (normally the module path)
+  pub fn app(self, app: &str) -> LogConfig {
+    LogConfig { facility: self.facility, host: self.host, app: Some(app.to_owned()), pid: self.pid, max_level: self.max_level, stderr: self.stderr }
+  }
+
+  /// Returns a new LogConfig configured with the provided default pid
+  pub fn pid(self, pid: &str) -> LogConfig {
+    LogConfig { facility: self.facility, host: self.host, app: self.app, pid: Some(pid.to_owned()), max_level: self.max_level, stderr: self.stderr }
+  }
+
+  /// Returns a new LogConfig that only lets records at or below the
+  /// given level through to the Syslog appender
+  pub fn max_level(self, max_level: log::LevelFilter) -> LogConfig {
+    LogConfig { facility: self.facility, host: self.host, app: self.app, pid: self.pid, max_level: max_level, stderr: self.stderr }
+  }
+
+  /// Returns a new LogConfig that also mirrors every formatted record to
+  /// standard error, useful for local debugging
+  pub fn stderr(self, stderr: bool) -> LogConfig {
+    LogConfig { facility: self.facility, host: self.host, app: self.app, pid: self.pid, max_level: self.max_level, stderr: stderr }
+  }
+}
+
+impl Default for LogConfig {
+  fn default() -> LogConfig {
+    LogConfig::new()
+  }
+}
+
+// whether the most recently logged record was truncated to fit max_len, for
+// callers going through the global log::Log facade, where the Syslog itself
+// (and its own truncated()) isn't reachable
+static FACADE_TRUNCATED: AtomicBool = AtomicBool::new(false);
+
+/// Returns whether the most recent record logged through the global `log`
+/// facade (installed via `init`/`init_with`) was truncated to fit the
+/// Syslog appender's `max_len`. See `Syslog::truncated` for the equivalent
+/// when logging through a directly-held `&mut Syslog`
+pub fn facade_truncated() -> bool {
+  FACADE_TRUNCATED.load(Ordering::Relaxed)
+}
+
+// adapts a Syslog appender to the log::Log trait, serializing concurrent
+// sends behind a Mutex since log::Log::log only gives us &self
+struct SyslogLogger {
+  syslog: Mutex<Syslog>,
+  max_level: log::LevelFilter,
+  stderr: bool
+}
+
+impl log::Log for SyslogLogger {
+  fn enabled(&self, metadata: &log::Metadata) -> bool {
+    metadata.level() <= self.max_level
+  }
+
+  fn log(&self, record: &log::Record) {
+    if !self.enabled(record.metadata()) {
+      return;
+    }
+    let severity = severity_from_level(record.level());
+    let msg = record.args().to_string();
+    let mut syslog = match self.syslog.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner()
+    };
+    // an app-name configured on the Syslog/LogConfig is an explicit override;
+    // record.target() (almost never empty) only fills in when none was set
+    let target = record.target();
+    let app = match syslog.app {
+      Some(ref app) => Some(app.clone()),
+      None if !target.is_empty() => Some(target.to_owned()),
+      None => None
+    };
+    let priority = Syslog::priority(syslog.facility.clone(), severity);
+    let pid = syslog.pid.clone();
+    LINE_BUF.with(|cell| {
+      let mut buf = cell.borrow_mut();
+      buf.clear();
+      let header_len = match syslog.protocol {
+        Protocol::Rfc5424 => Syslog::write_line_5424(
+          &mut buf, syslog.facility.clone(), severity, time::now(),
+          syslog.host.as_ref().map(Deref::deref), app.as_ref().map(Deref::deref),
+          syslog.pid.as_ref().map(Deref::deref), syslog.msgid.as_ref().map(Deref::deref),
+          &syslog.structured_data, &msg, syslog.bom),
+        Protocol::Rfc3164 => Syslog::write_line_3164(
+          &mut buf, syslog.facility.clone(), severity, time::now(),
+          syslog.host.as_ref().map(Deref::deref), app.as_ref().map(Deref::deref),
+          syslog.pid.as_ref().map(Deref::deref), &msg)
+      };
+      syslog.truncated = Syslog::truncate_to(&mut buf, header_len, syslog.max_len);
+      FACADE_TRUNCATED.store(syslog.truncated, Ordering::Relaxed);
+      if self.stderr {
+        let _ = writeln!(io::stderr(), "{}", buf);
+      }
+      let _ = syslog.transport.send(priority, &buf, &msg, app.as_ref().map(Deref::deref), pid.as_ref().map(Deref::deref));
+    });
+  }
+
+  fn flush(&self) {}
+}
+
+/// Installs the given Syslog appender as the global `log` backend using
+/// a default LogConfig
+pub fn init(syslog: Syslog) -> ::std::result::Result<(), log::SetLoggerError> {
+  init_with(syslog, LogConfig::new())
+}
+
+/// Installs the given Syslog appender as the global `log` backend,
+/// applying the facility/host/app/pid overrides and max level from the
+/// provided LogConfig
+pub fn init_with(mut syslog: Syslog, config: LogConfig) -> ::std::result::Result<(), log::SetLoggerError> {
+  if let Some(facility) = config.facility {
+    syslog.facility = facility;
+  }
+  if config.host.is_some() {
+    syslog.host = config.host;
+  }
+  if config.app.is_some() {
+    syslog.app = config.app;
+  }
+  if config.pid.is_some() {
+    syslog.pid = config.pid;
+  }
+  let logger = SyslogLogger { syslog: Mutex::new(syslog), max_level: config.max_level, stderr: config.stderr };
+  // leaked once per process, for the life of the global logger
+  let logger: &'static SyslogLogger = Box::leak(Box::new(logger));
+  log::set_logger(logger)?;
+  log::set_max_level(config.max_level);
+  Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-  use super::{Syslog, Facility, Severity};
-  use time;
+  use super::{Syslog, Facility, Severity, StructuredData};
+  use time::{self, Tm};
   //use test::Bencher;
 
+  fn test_tm() -> Tm {
+    Tm {
+      tm_sec: 5, tm_min: 4, tm_hour: 3, tm_mday: 2, tm_mon: 9, tm_year: 118,
+      tm_wday: 2, tm_yday: 274, tm_isdst: 0, tm_utcoff: 0, tm_nsec: 0
+    }
+  }
+
   #[test]
   fn test_syslog_line_defaults() {
     let ts = time::now();
     assert_eq!(Syslog::line(
-      Facility::LOCAL0, Severity::INFO, ts, None, None, None, None, "yo"),
-      format!("<134>1 {} - - - - yo", ts.rfc3339()));
+      Facility::LOCAL0, Severity::INFO, ts, None, None, None, None, &[], "yo"),
+      format!("<134>1 {} - - - - - yo", ts.rfc3339()));
   }
 
   #[test]
@@ -313,8 +1016,8 @@ mod tests {
     let ts = time::now();
     let host = "foo.local";
     assert_eq!(Syslog::line(
-      Facility::LOCAL0, Severity::INFO, ts, Some(host), None, None, None, "yo"),
-      format!("<134>1 {} {} - - - yo", ts.rfc3339(), host));
+      Facility::LOCAL0, Severity::INFO, ts, Some(host), None, None, None, &[], "yo"),
+      format!("<134>1 {} {} - - - - yo", ts.rfc3339(), host));
   }
 
   #[test]
@@ -322,8 +1025,8 @@ mod tests {
     let ts = time::now();
     let app = "sysly";
     assert_eq!(Syslog::line(
-      Facility::LOCAL0, Severity::INFO, ts, None, Some(app), None, None, "yo"),
-      format!("<134>1 {} - {} - - yo", ts.rfc3339(), app));
+      Facility::LOCAL0, Severity::INFO, ts, None, Some(app), None, None, &[], "yo"),
+      format!("<134>1 {} - {} - - - yo", ts.rfc3339(), app));
   }
 
   #[test]
@@ -331,8 +1034,8 @@ mod tests {
     let ts = time::now();
     let pid = "16";
     assert_eq!(Syslog::line(
-      Facility::LOCAL0, Severity::INFO, ts, None, None, Some(pid), None, "yo"),
-      format!("<134>1 {} - - {} - yo", ts.rfc3339(), pid));
+      Facility::LOCAL0, Severity::INFO, ts, None, None, Some(pid), None, &[], "yo"),
+      format!("<134>1 {} - - {} - - yo", ts.rfc3339(), pid));
   }
 
   #[test]
@@ -340,13 +1043,136 @@ mod tests {
     let ts = time::now();
     let msgid = "TCPIN";
     assert_eq!(Syslog::line(
-      Facility::LOCAL0, Severity::INFO, ts, None, None, None, Some(msgid), "yo"),
-      format!("<134>1 {} - - - {} yo", ts.rfc3339(), msgid));
+      Facility::LOCAL0, Severity::INFO, ts, None, None, None, Some(msgid), &[], "yo"),
+      format!("<134>1 {} - - - {} - yo", ts.rfc3339(), msgid));
+  }
+
+  #[test]
+  fn test_syslog_line_structured_data_nil() {
+    let ts = time::now();
+    assert_eq!(Syslog::line(
+      Facility::LOCAL0, Severity::INFO, ts, None, None, None, None, &[], "yo"),
+      format!("<134>1 {} - - - - - yo", ts.rfc3339()));
+  }
+
+  #[test]
+  fn test_syslog_line_structured_data_single_element() {
+    let ts = time::now();
+    let sd = StructuredData::new("exampleSDID@32473").param("iut", "3");
+    assert_eq!(Syslog::line(
+      Facility::LOCAL0, Severity::INFO, ts, None, None, None, None, &[sd], "yo"),
+      format!("<134>1 {} - - - - [exampleSDID@32473 iut=\"3\"] yo", ts.rfc3339()));
+  }
+
+  #[test]
+  fn test_syslog_line_structured_data_multiple_elements() {
+    let ts = time::now();
+    let a = StructuredData::new("a@1").param("x", "1");
+    let b = StructuredData::new("b@2").param("y", "2");
+    assert_eq!(Syslog::line(
+      Facility::LOCAL0, Severity::INFO, ts, None, None, None, None, &[a, b], "yo"),
+      format!("<134>1 {} - - - - [a@1 x=\"1\"][b@2 y=\"2\"] yo", ts.rfc3339()));
+  }
+
+  #[test]
+  fn test_syslog_line_structured_data_escapes_reserved_chars() {
+    let ts = time::now();
+    let sd = StructuredData::new("id").param("name", "a\"b\\c]d");
+    assert_eq!(Syslog::line(
+      Facility::LOCAL0, Severity::INFO, ts, None, None, None, None, &[sd], "yo"),
+      format!("<134>1 {} - - - - [id name=\"a\\\"b\\\\c\\]d\"] yo", ts.rfc3339()));
+  }
+
+  #[test]
+  fn test_syslog_line_3164_defaults() {
+    let ts = test_tm();
+    assert_eq!(Syslog::line_3164(
+      Facility::LOCAL0, Severity::INFO, ts, None, None, None, "yo"),
+      "<134>Oct  2 03:04:05 - -: yo");
+  }
+
+  #[test]
+  fn test_syslog_line_3164_host() {
+    let ts = test_tm();
+    let host = "foo.local";
+    assert_eq!(Syslog::line_3164(
+      Facility::LOCAL0, Severity::INFO, ts, Some(host), None, None, "yo"),
+      "<134>Oct  2 03:04:05 foo.local -: yo");
+  }
+
+  #[test]
+  fn test_syslog_line_3164_app() {
+    let ts = test_tm();
+    let app = "sysly";
+    assert_eq!(Syslog::line_3164(
+      Facility::LOCAL0, Severity::INFO, ts, None, Some(app), None, "yo"),
+      "<134>Oct  2 03:04:05 - sysly: yo");
+  }
+
+  #[test]
+  fn test_syslog_line_3164_pid() {
+    let ts = test_tm();
+    let app = "sysly";
+    let pid = "16";
+    assert_eq!(Syslog::line_3164(
+      Facility::LOCAL0, Severity::INFO, ts, None, Some(app), Some(pid), "yo"),
+      "<134>Oct  2 03:04:05 - sysly[16]: yo");
+  }
+
+  #[test]
+  fn test_write_line_5424_bom() {
+    let ts = test_tm();
+    let mut buf = String::new();
+    // the BOM is placed ahead of header_len, so a later truncation to max_len never strips it
+    let header_len = Syslog::write_line_5424(
+      &mut buf, Facility::LOCAL0, Severity::INFO, ts, None, None, None, None, &[], "yo", true);
+    assert!(buf[..header_len].ends_with("\u{feff}"));
+    assert_eq!(&buf[header_len..], "yo");
+  }
+
+  #[test]
+  fn test_write_line_5424_no_bom() {
+    let ts = test_tm();
+    let mut buf = String::new();
+    let header_len = Syslog::write_line_5424(
+      &mut buf, Facility::LOCAL0, Severity::INFO, ts, None, None, None, None, &[], "yo", false);
+    assert_eq!(&buf[header_len..], "yo");
+  }
+
+  #[test]
+  fn test_truncate_to_no_max_len() {
+    let mut buf = "header: hello world".to_owned();
+    assert!(!Syslog::truncate_to(&mut buf, 8, None));
+    assert_eq!(buf, "header: hello world");
+  }
+
+  #[test]
+  fn test_truncate_to_under_max_len() {
+    let mut buf = "header: hi".to_owned();
+    assert!(!Syslog::truncate_to(&mut buf, 8, Some(100)));
+    assert_eq!(buf, "header: hi");
+  }
+
+  #[test]
+  fn test_truncate_to_cuts_msg_not_header() {
+    let mut buf = "header: hello world".to_owned();
+    assert!(Syslog::truncate_to(&mut buf, 8, Some(13)));
+    assert_eq!(buf, "header: hello");
+  }
+
+  #[test]
+  fn test_truncate_to_respects_char_boundary() {
+    let mut buf = String::from("header: ");
+    let header_len = buf.len();
+    buf.push_str("na\u{ef}ve");
+    // cutting at header_len + 3 would land inside the 2-byte 'i with diaeresis'
+    assert!(Syslog::truncate_to(&mut buf, header_len, Some(header_len + 3)));
+    assert_eq!(buf, "header: na");
   }
 
   //#[bench]
   //fn bench_assembly_line(b: &mut Bencher) {
   // b.iter(|| Syslog::line(
-  //    Facility::LOCAL0, Severity::INFO, time::now(), None, None, None, None, "yo"))
+  //    Facility::LOCAL0, Severity::INFO, time::now(), None, None, None, None, &[], "yo"))
   //}
 }